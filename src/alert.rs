@@ -8,6 +8,7 @@ use color_eyre::{
 use reqwest::{IntoUrl, Url};
 
 use crate::config_file::Prometheus;
+use crate::Registry;
 
 #[derive(Clone, Debug)]
 pub struct AlertFilter {
@@ -40,7 +41,35 @@ impl AlertChecker {
         })
     }
 
+    /// Replace the set of alerts this checker filters for in place, e.g. after a config reload.
+    pub fn set_alert_set(&mut self, filters: impl IntoIterator<Item = AlertFilter>) {
+        self.alert_set = filters.into_iter().collect();
+    }
+
     pub async fn check(&mut self) -> Result<Vec<Alert>, Report> {
+        let alerts = self.fetch_alerts().await?;
+        Ok(alerts
+            .into_iter()
+            .filter(|a| self.alert_set.iter().any(|f| a.check(f)))
+            .collect())
+    }
+
+    /// Like [`Self::check`], but additionally records each configured alert filter's
+    /// firing/not-firing status into `registry`, keyed by this checker's URL.
+    pub async fn check_into_registry(&mut self, registry: &Registry) -> Result<Vec<Alert>, Report> {
+        let alerts = self.fetch_alerts().await?;
+        for filter in &self.alert_set {
+            let firing = alerts.iter().any(|a| a.check(filter));
+            registry.record(self.url.as_str(), &filter.name, "alert_firing", firing);
+        }
+
+        Ok(alerts
+            .into_iter()
+            .filter(|a| self.alert_set.iter().any(|f| a.check(f)))
+            .collect())
+    }
+
+    async fn fetch_alerts(&self) -> Result<Vec<Alert>, Report> {
         let body = self
             .client
             .get(self.url.clone())
@@ -59,10 +88,7 @@ impl AlertChecker {
             bail!("AlertChecker: response indicates error");
         }
 
-        Ok(alerts
-            .into_iter()
-            .filter(|a| self.alert_set.iter().any(|f| a.check(f)))
-            .collect())
+        Ok(alerts)
     }
 }
 
@@ -1,5 +1,7 @@
-use color_eyre::eyre::{eyre, Report, WrapErr};
-use wtf_prometheus_agent::AlertChecker;
+use std::sync::Arc;
+
+use color_eyre::eyre::{eyre, Context, Report};
+use wtf_prometheus_agent::{serve_exporter, AlertChecker, Daemon, Registry};
 
 fn get_config_file_arg() -> Option<String> {
     let mut args = std::env::args().take(3).skip(1);
@@ -19,12 +21,25 @@ fn main() -> Result<(), Report> {
 
     let cfg_file =
         get_config_file_arg().ok_or_else(|| eyre!("Usage: alerts -c <config_file.toml>"))?;
-    let cfg = wtf_prometheus_agent::parse_config(cfg_file)?;
-    let mut alert_checker: AlertChecker = cfg.prometheus.try_into()?;
+    let cfg = wtf_prometheus_agent::parse_config(&cfg_file)?;
+    let exporter = cfg.exporter.clone();
+    let interval = cfg.prometheus.interval.to_std()?;
+    let alert_checker: AlertChecker = cfg.prometheus.try_into()?;
+
+    let registry = exporter
+        .as_ref()
+        .map(|e| e.flush_window.to_std().map(|d| Arc::new(Registry::new(d))))
+        .transpose()
+        .wrap_err("exporter.flush_window out of range")?;
+
     rt.block_on(async move {
-        let alerts = alert_checker.check().await.wrap_err("query alerts")?;
-        println!("{:?}", alerts);
-        Ok::<_, Report>(())
+        if let (Some(listen), Some(registry)) = (exporter.map(|e| e.listen), registry.clone()) {
+            tokio::spawn(serve_exporter(listen, registry));
+        }
+        Daemon::for_alerts(vec![(alert_checker, interval)])
+            .with_registry(registry)
+            .run_with_reload(cfg_file)
+            .await
     })?;
     Ok(())
 }
@@ -1,5 +1,7 @@
+use std::sync::Arc;
+
 use color_eyre::eyre::{eyre, Context, Report};
-use wtf_prometheus_agent::ElementHealth;
+use wtf_prometheus_agent::{serve_exporter, Daemon, ElementHealth, Registry};
 
 fn get_config_file_arg() -> Option<String> {
     let mut args = std::env::args().take(3).skip(1);
@@ -19,20 +21,34 @@ fn main() -> Result<(), Report> {
 
     let cfg_file =
         get_config_file_arg().ok_or_else(|| eyre!("Usage: fetch_one -c <config_file.toml>"))?;
-    let cfg = wtf_prometheus_agent::parse_config(cfg_file)?;
-    let mut elements: Vec<ElementHealth> = cfg
+    let cfg = wtf_prometheus_agent::parse_config(&cfg_file)?;
+    let exporter = cfg.exporter.clone();
+    let state_store = cfg.state_store.clone();
+    let elements: Vec<(ElementHealth, std::time::Duration)> = cfg
         .elements
         .into_iter()
-        .map(|e| e.try_into())
+        .map(|e| {
+            let interval = e.interval.to_std()?;
+            let element = ElementHealth::try_from_config(e, state_store.as_ref())?;
+            Ok::<_, Report>((element, interval))
+        })
         .collect::<Result<_, _>>()
         .wrap_err("Could not create ElementHealth checkers from config file")?;
 
+    let registry = exporter
+        .as_ref()
+        .map(|e| e.flush_window.to_std().map(|d| Arc::new(Registry::new(d))))
+        .transpose()
+        .wrap_err("exporter.flush_window out of range")?;
+
     rt.block_on(async move {
-        for el in &mut elements {
-            let triggered_samples = el.check().await?;
-            dbg!(triggered_samples);
+        if let (Some(listen), Some(registry)) = (exporter.map(|e| e.listen), registry.clone()) {
+            tokio::spawn(serve_exporter(listen, registry));
         }
-        Ok::<_, Report>(())
+        Daemon::for_elements(elements)
+            .with_registry(registry)
+            .run_with_reload(cfg_file)
+            .await
     })?;
     Ok(())
 }
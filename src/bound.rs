@@ -26,6 +26,26 @@ impl Bound {
         }
     }
 
+    /// The TOML `bound_type` string for this bound, reused as the exporter's `bound` label.
+    pub fn descriptor(&self) -> &'static str {
+        match self {
+            Self::AbsLower(_) => "abs_lower",
+            Self::AbsUpper(_) => "abs_upper",
+            Self::RateLower { .. } => "rate_lower",
+            Self::RateUpper { .. } => "rate_upper",
+        }
+    }
+
+    /// The window a relative bound computes its rate over, if it is one.
+    pub fn time_period(&self) -> Option<chrono::Duration> {
+        match self {
+            Self::AbsLower(_) | Self::AbsUpper(_) => None,
+            Self::RateLower { time_period, .. } | Self::RateUpper { time_period, .. } => {
+                Some(*time_period)
+            }
+        }
+    }
+
     pub fn check(
         &self,
         value: &Value,
@@ -51,12 +51,47 @@ pub fn parse_config_str(cfg: &str) -> Result<Config, Report> {
 pub struct Config {
     pub prometheus: Prometheus,
     pub elements: Vec<Element>,
+    pub exporter: Option<ExporterConfig>,
+    pub state_store: Option<StateStoreConfig>,
+}
+
+/// `[state_store]`: where `RateLower`/`RateUpper` bounds persist their sample history.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StateStoreConfig {
+    /// `"memory"` (default behavior if this table is absent) or `"sled"`, the latter requiring
+    /// the crate's `sled-backend` feature.
+    pub backend: String,
+    /// Directory to store the persistent database in. Required for the `sled` backend.
+    pub path: Option<std::path::PathBuf>,
+}
+
+/// `[exporter]`: serve this agent's own health-check results as a Prometheus endpoint.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExporterConfig {
+    /// Address to listen on, e.g. `"0.0.0.0:9469"`.
+    pub listen: std::net::SocketAddr,
+    /// Drop a series if its owning element hasn't reported within this window.
+    #[serde(
+        default = "default_flush_window",
+        deserialize_with = "duration_str::deserialize_duration_chrono"
+    )]
+    pub flush_window: Duration,
+}
+
+fn default_flush_window() -> Duration {
+    Duration::minutes(5)
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Prometheus {
     pub url: String,
     pub alerts: Vec<AlertSpec>,
+    /// How often the daemon re-polls the alerts endpoint.
+    #[serde(
+        default = "default_poll_interval",
+        deserialize_with = "duration_str::deserialize_duration_chrono"
+    )]
+    pub interval: Duration,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -78,6 +113,16 @@ impl From<AlertSpec> for crate::alert::AlertFilter {
 pub struct Element {
     pub url: String,
     pub bounds: Vec<FilterSpec>,
+    /// How often the daemon re-scrapes this element.
+    #[serde(
+        default = "default_poll_interval",
+        deserialize_with = "duration_str::deserialize_duration_chrono"
+    )]
+    pub interval: Duration,
+}
+
+fn default_poll_interval() -> Duration {
+    Duration::seconds(30)
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -90,6 +135,14 @@ pub struct FilterSpec {
         deserialize_with = "duration_str::deserialize_option_duration_chrono"
     )]
     period: Option<Duration>,
+    /// How `metric_name` selects the metrics it applies to: `"exact"` (default), `"glob"`, or
+    /// `"regex"`.
+    #[serde(default = "default_match_type")]
+    match_type: String,
+}
+
+fn default_match_type() -> String {
+    "exact".to_owned()
 }
 
 impl TryFrom<FilterSpec> for crate::element::Filter {
@@ -114,9 +167,25 @@ impl TryFrom<FilterSpec> for crate::element::Filter {
             s => bail!("Unsupported bound type {:?}", s),
         };
 
-        Ok(crate::element::Filter::Exact {
-            metric_name: value.metric_name,
-            trigger: b,
-        })
+        match value.match_type.to_lowercase().as_str() {
+            "exact" => Ok(crate::element::Filter::Exact {
+                metric_name: value.metric_name,
+                trigger: b,
+            }),
+            "glob" => {
+                let metric_glob = glob::Pattern::new(&value.metric_name)
+                    .wrap_err_with(|| format!("invalid glob pattern {:?}", value.metric_name))?;
+                Ok(crate::element::Filter::Glob {
+                    metric_glob,
+                    trigger: b,
+                })
+            }
+            "regex" => {
+                let metric_re = regex::Regex::new(&value.metric_name)
+                    .wrap_err_with(|| format!("invalid regex {:?}", value.metric_name))?;
+                Ok(crate::element::Filter::Regex { metric_re, trigger: b })
+            }
+            s => bail!("Unsupported match_type {:?}", s),
+        }
     }
 }
@@ -0,0 +1,298 @@
+//! Long-running scheduler that repeatedly drives `ElementHealth` and `AlertChecker` checks, each
+//! on its own interval, instead of the one-shot `check()` pass the CLIs used to do.
+//!
+//! The queue is a time-ordered `BTreeMap<Instant, TaskId>`: the loop peeks the smallest key, runs
+//! that task if it's due, and otherwise sleeps until it is (or wakes early on ctrl-c/SIGHUP). This
+//! keeps the daemon idle between ticks rather than busy-polling, and lets each element/alert poll
+//! on its own cadence instead of hammering every endpoint at the same rate.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::Report;
+
+use crate::{AlertChecker, ElementHealth, Registry};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum TaskId {
+    /// Keyed by element URL rather than a vector index, since `reload` can add/remove elements
+    /// out from under the scheduler while it runs.
+    Element(String),
+    Alert(usize),
+}
+
+/// Schedules periodic `ElementHealth`/`AlertChecker` checks on independent intervals.
+pub struct Daemon {
+    elements: HashMap<String, (ElementHealth, Duration)>,
+    alerts: Vec<(AlertChecker, Duration)>,
+    queue: BTreeMap<Instant, TaskId>,
+    registry: Option<Arc<Registry>>,
+    /// Whether `reload` should diff `[[elements]]` from the reloaded config into `self.elements`.
+    /// A `Daemon` constructed to run only one subsystem (e.g. the `alerts` binary's daemon, which
+    /// shares its config file with `fetch_one`'s `[[elements]]` table) must leave the other
+    /// subsystem's table alone rather than silently picking up work it was never given.
+    owns_elements: bool,
+    /// Like `owns_elements`, but for `[prometheus.alerts]`.
+    owns_alerts: bool,
+}
+
+impl Daemon {
+    /// A `Daemon` that owns only the element-scraping subsystem (e.g. the `fetch_one` binary):
+    /// `reload` will add/remove/update `[[elements]]` entries but never touch
+    /// `[prometheus.alerts]`, even if the reloaded config file happens to define some.
+    pub fn for_elements(elements: Vec<(ElementHealth, Duration)>) -> Self {
+        Self::new_inner(elements, Vec::new(), None, true, false)
+    }
+
+    /// A `Daemon` that owns only the alert-checking subsystem (e.g. the `alerts` binary):
+    /// `reload` will update `[prometheus.alerts]` but never touch `[[elements]]`, even if the
+    /// reloaded config file happens to define some.
+    pub fn for_alerts(alerts: Vec<(AlertChecker, Duration)>) -> Self {
+        Self::new_inner(Vec::new(), alerts, None, false, true)
+    }
+
+    /// Record each check's results into `registry` (when given) so they're scrapable via the
+    /// exporter.
+    pub fn with_registry(mut self, registry: Option<Arc<Registry>>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    fn new_inner(
+        elements: Vec<(ElementHealth, Duration)>,
+        alerts: Vec<(AlertChecker, Duration)>,
+        registry: Option<Arc<Registry>>,
+        owns_elements: bool,
+        owns_alerts: bool,
+    ) -> Self {
+        let elements: HashMap<String, (ElementHealth, Duration)> = elements
+            .into_iter()
+            .map(|(el, interval)| (el.url().to_owned(), (el, interval)))
+            .collect();
+
+        let mut queue = BTreeMap::new();
+        let now = Instant::now();
+        for url in elements.keys() {
+            Self::schedule(&mut queue, now, TaskId::Element(url.clone()));
+        }
+        for i in 0..alerts.len() {
+            Self::schedule(&mut queue, now, TaskId::Alert(i));
+        }
+
+        Self {
+            elements,
+            alerts,
+            queue,
+            registry,
+            owns_elements,
+            owns_alerts,
+        }
+    }
+
+    /// Insert `task` at `at`, nudging forward by a nanosecond on collision so two tasks due at
+    /// the same instant don't clobber each other in the map.
+    fn schedule(queue: &mut BTreeMap<Instant, TaskId>, mut at: Instant, task: TaskId) {
+        while queue.contains_key(&at) {
+            at += Duration::from_nanos(1);
+        }
+        queue.insert(at, task);
+    }
+
+    /// Run the scheduler until a SIGINT (ctrl-c) is received.
+    pub async fn run(self) -> Result<(), Report> {
+        self.run_inner(None).await
+    }
+
+    /// Like [`Self::run`], but also re-reads `config_path` and applies any changes via
+    /// [`Self::reload`] whenever the process receives SIGHUP.
+    pub async fn run_with_reload(self, config_path: impl Into<PathBuf>) -> Result<(), Report> {
+        self.run_inner(Some(config_path.into())).await
+    }
+
+    async fn run_inner(mut self, config_path: Option<PathBuf>) -> Result<(), Report> {
+        let mut sighup = config_path
+            .is_some()
+            .then(|| tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()))
+            .transpose()?;
+
+        loop {
+            // An empty queue (e.g. no elements or alerts configured) just means there's nothing
+            // due for a long time; idle rather than busy-loop.
+            let next = self
+                .queue
+                .keys()
+                .next()
+                .copied()
+                .unwrap_or_else(|| Instant::now() + Duration::from_secs(86400));
+
+            let now = Instant::now();
+            if next > now {
+                tokio::select! {
+                    _ = tokio::time::sleep(next - now) => {}
+                    res = tokio::signal::ctrl_c() => {
+                        res?;
+                        return Ok(());
+                    }
+                    _ = recv_sighup(&mut sighup), if sighup.is_some() => {
+                        if let Some(path) = &config_path {
+                            match self.reload(path) {
+                                Ok(()) => eprintln!("config reloaded from {}", path.display()),
+                                Err(e) => {
+                                    eprintln!("config reload failed, keeping previous config: {e:?}")
+                                }
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let (_, task) = self
+                .queue
+                .pop_first()
+                .expect("queue non-empty, checked above");
+            if let Some(interval) = self.run_task(&task).await {
+                Self::schedule(&mut self.queue, Instant::now() + interval, task);
+            }
+            // else: the element/alert this task pointed to was dropped by a reload; let it fall
+            // out of the queue rather than rescheduling it.
+        }
+    }
+
+    async fn run_task(&mut self, task: &TaskId) -> Option<Duration> {
+        match task {
+            TaskId::Element(url) => {
+                let (element, interval) = self.elements.get_mut(url)?;
+                let result = match &self.registry {
+                    Some(registry) => element.check_into_registry(registry).await,
+                    None => element.check().await,
+                };
+                match result {
+                    Ok(triggered) if !triggered.is_empty() => {
+                        eprintln!("element[{url}] triggered: {triggered:?}")
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("element[{url}] check failed: {e:?}"),
+                }
+                Some(*interval)
+            }
+            TaskId::Alert(i) => {
+                let (checker, interval) = self.alerts.get_mut(*i)?;
+                let result = match &self.registry {
+                    Some(registry) => checker.check_into_registry(registry).await,
+                    None => checker.check().await,
+                };
+                match result {
+                    Ok(firing) if !firing.is_empty() => {
+                        eprintln!("alerts[{i}] firing: {firing:?}")
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("alerts[{i}] check failed: {e:?}"),
+                }
+                Some(*interval)
+            }
+        }
+    }
+
+    /// Re-parse the config at `path` and apply any changes to the running element/alert set in
+    /// place: add newly-listed elements, drop removed ones, and update the bounds of elements
+    /// whose `bounds` changed, preserving `relative_state` for metrics that still exist. A
+    /// `Daemon` only ever diffs the subsystem(s) it was constructed to own (see
+    /// [`Self::for_elements`]/[`Self::for_alerts`]) - e.g. the `alerts` binary's daemon ignores
+    /// `[[elements]]` entirely, even though it shares a config file with `fetch_one` where that
+    /// table is normally present. New elements honor `[state_store]` the same way startup does.
+    ///
+    /// If `path` fails to parse, or any owned entry in it fails to convert (e.g. a bad
+    /// glob/regex, or a `rate_*` bound missing its time period), this returns the error and
+    /// leaves the current config running untouched - a bad edit should never take the daemon
+    /// offline, and must never apply only partway through. To guarantee that, every change is
+    /// fully validated (and, for brand new elements, fully constructed) into local state before
+    /// anything in `self` is mutated.
+    pub fn reload(&mut self, path: impl AsRef<Path>) -> Result<(), Report> {
+        let cfg = crate::parse_config(path)?;
+
+        let staged_elements = if self.owns_elements {
+            let mut staged = Vec::with_capacity(cfg.elements.len());
+            for element_cfg in cfg.elements {
+                let url = element_cfg.url.clone();
+                let interval = element_cfg.interval.to_std()?;
+                let update = if self.elements.contains_key(&url) {
+                    let filters: Vec<crate::element::Filter> = element_cfg
+                        .bounds
+                        .into_iter()
+                        .map(|b| b.try_into())
+                        .collect::<Result<_, Report>>()?;
+                    StagedElement::Update(filters, interval)
+                } else {
+                    StagedElement::Insert(
+                        ElementHealth::try_from_config(element_cfg, cfg.state_store.as_ref())?,
+                        interval,
+                    )
+                };
+                staged.push((url, update));
+            }
+            Some(staged)
+        } else {
+            None
+        };
+
+        let staged_alerts = if self.owns_alerts {
+            let alert_filters: Vec<crate::alert::AlertFilter> =
+                cfg.prometheus.alerts.into_iter().map(Into::into).collect();
+            let alert_interval = cfg.prometheus.interval.to_std()?;
+            Some((alert_filters, alert_interval))
+        } else {
+            None
+        };
+
+        // Every owned subsystem's new config parsed and converted successfully; only now do we
+        // touch `self`, and every remaining step here is infallible.
+        if let Some(staged) = staged_elements {
+            let seen: HashSet<&str> = staged.iter().map(|(url, _)| url.as_str()).collect();
+            for (url, update) in staged {
+                match update {
+                    StagedElement::Update(filters, interval) => {
+                        let (existing, existing_interval) = self
+                            .elements
+                            .get_mut(&url)
+                            .expect("checked contains_key above");
+                        existing.set_filters(filters);
+                        *existing_interval = interval;
+                    }
+                    StagedElement::Insert(element, interval) => {
+                        self.elements.insert(url.clone(), (element, interval));
+                        Self::schedule(&mut self.queue, Instant::now(), TaskId::Element(url));
+                    }
+                }
+            }
+            self.elements.retain(|url, _| seen.contains(url.as_str()));
+        }
+
+        if let Some((alert_filters, alert_interval)) = staged_alerts {
+            if let Some((checker, existing_interval)) = self.alerts.get_mut(0) {
+                checker.set_alert_set(alert_filters);
+                *existing_interval = alert_interval;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A validated, not-yet-applied change to one element, produced by [`Daemon::reload`] before it
+/// touches `self` so a later element's conversion failure can't leave an earlier one half-applied.
+enum StagedElement {
+    Update(Vec<crate::element::Filter>, Duration),
+    Insert(ElementHealth, Duration),
+}
+
+async fn recv_sighup(sighup: &mut Option<tokio::signal::unix::Signal>) {
+    match sighup {
+        Some(s) => {
+            s.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
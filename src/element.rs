@@ -1,18 +1,80 @@
 //! Manually monitor individual Prometheus-compatible endpoints
 
-use color_eyre::eyre::{Context, Report};
+use color_eyre::eyre::{eyre, Context, Report};
 use prometheus_parse::Sample;
 use reqwest::{IntoUrl, Url};
 use std::collections::HashMap;
 
-use crate::{config_file, Bound};
+use crate::{config_file, Bound, MemoryStateStore, Registry, StateStore};
 
 /// Describes when to set health bits on Prometheus metrics
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Filter {
     Exact { metric_name: String, trigger: Bound },
-    //Glob { metric_glob: glob::Pattern, trigger: Bound },
-    //Regex { metric_re: String, trigger: Bound },
+    Glob { metric_glob: glob::Pattern, trigger: Bound },
+    Regex { metric_re: regex::Regex, trigger: Bound },
+}
+
+/// The compiled form of a checker's [`Filter`]s. `Exact` filters are kept in a `HashMap` so
+/// matching them stays O(1); `Glob`/`Regex` filters are compiled once here and then tested
+/// against each scraped metric name in turn, since they can't be indexed the same way.
+#[derive(Default)]
+struct CompiledFilters {
+    exact: HashMap<String, Vec<Bound>>,
+    globs: Vec<(glob::Pattern, Bound)>,
+    regexes: Vec<(regex::Regex, Bound)>,
+}
+
+impl CompiledFilters {
+    fn new(filters: impl IntoIterator<Item = Filter>) -> Self {
+        filters.into_iter().fold(Self::default(), |mut acc, f| {
+            match f {
+                Filter::Exact {
+                    metric_name,
+                    trigger,
+                } => acc.exact.entry(metric_name).or_default().push(trigger),
+                Filter::Glob {
+                    metric_glob,
+                    trigger,
+                } => acc.globs.push((metric_glob, trigger)),
+                Filter::Regex {
+                    metric_re,
+                    trigger,
+                } => acc.regexes.push((metric_re, trigger)),
+            }
+            acc
+        })
+    }
+
+    /// The bounds that apply to `metric`, via the exact-match map or a matching glob/regex.
+    fn matching(&self, metric: &str) -> Vec<Bound> {
+        let mut matches: Vec<Bound> = self
+            .exact
+            .get(metric)
+            .map(|bounds| bounds.iter().copied().collect())
+            .unwrap_or_default();
+        matches.extend(
+            self.globs
+                .iter()
+                .filter(|(pattern, _)| pattern.matches(metric))
+                .map(|(_, bound)| *bound),
+        );
+        matches.extend(
+            self.regexes
+                .iter()
+                .filter(|(re, _)| re.is_match(metric))
+                .map(|(_, bound)| *bound),
+        );
+        matches
+    }
+
+    fn all_bounds(&self) -> impl Iterator<Item = &Bound> {
+        self.exact
+            .values()
+            .flatten()
+            .chain(self.globs.iter().map(|(_, b)| b))
+            .chain(self.regexes.iter().map(|(_, b)| b))
+    }
 }
 
 /// An agent responsible for monitoring a single Prometheus endpoint and returning anomalous
@@ -22,10 +84,12 @@ pub struct ElementHealth {
     url: Url,
     client: reqwest::Client,
     /// the Filters to check
-    // TODO HashMap won't work if Filters can be globs or regexes, unless we do some pre-processing.
-    filter_set: HashMap<String, Vec<Bound>>,
-    /// metric_name -> last observed Sample
-    relative_state: HashMap<String, Sample>,
+    filters: CompiledFilters,
+    /// sample history backing `RateLower`/`RateUpper` bounds
+    relative_state: Box<dyn StateStore>,
+    /// the largest `time_period` across all relative bounds, i.e. how far back `relative_state`
+    /// needs to retain samples
+    max_relative_window: chrono::Duration,
 }
 
 impl TryFrom<config_file::Element> for ElementHealth {
@@ -47,26 +111,109 @@ impl ElementHealth {
         url: impl IntoUrl,
         filter_set: impl IntoIterator<Item = Filter>,
     ) -> Result<Self, Report> {
+        Self::with_state_store(url, filter_set, Box::new(MemoryStateStore::default()))
+    }
+
+    /// Like the `TryFrom<config_file::Element>` impl, but honors the top-level `[state_store]`
+    /// table the same way startup does: `None` or `backend = "memory"` uses the default in-memory
+    /// store, and `backend = "sled"` (behind the `sled-backend` feature) opens a persistent store
+    /// rooted at `path`, keyed by this element's URL. Shared by `fetch_one`'s startup path and
+    /// `Daemon::reload`'s handling of newly-added elements, so both honor the setting the same way.
+    pub fn try_from_config(
+        element: config_file::Element,
+        state_store: Option<&config_file::StateStoreConfig>,
+    ) -> Result<Self, Report> {
+        match state_store {
+            Some(cfg) if cfg.backend == "sled" => {
+                #[cfg(feature = "sled-backend")]
+                {
+                    let base = cfg
+                        .path
+                        .as_deref()
+                        .ok_or_else(|| eyre!("state_store.backend = \"sled\" requires a path"))?;
+                    let db_path = base.join(sanitize_for_path(&element.url));
+                    let store = crate::SledStateStore::open(db_path)?;
+                    Self::try_from_config_with_state_store(element, Box::new(store))
+                }
+                #[cfg(not(feature = "sled-backend"))]
+                {
+                    Err(eyre!(
+                        "state_store.backend = \"sled\" requires building with the sled-backend feature"
+                    ))
+                }
+            }
+            Some(cfg) if cfg.backend == "memory" => element.try_into(),
+            Some(cfg) => Err(eyre!("unsupported state_store.backend {:?}", cfg.backend)),
+            None => element.try_into(),
+        }
+    }
+
+    /// Like the `TryFrom<config_file::Element>` impl, but backs relative-bound sample history
+    /// with `state_store` instead of the default in-memory one.
+    pub fn try_from_config_with_state_store(
+        value: config_file::Element,
+        state_store: Box<dyn StateStore>,
+    ) -> Result<Self, Report> {
+        let filters = value
+            .bounds
+            .into_iter()
+            .map(|b| b.try_into())
+            .collect::<Result<Vec<Filter>, _>>()?;
+        Self::with_state_store(value.url, filters, state_store)
+    }
+
+    /// Like [`Self::new`], but backs relative-bound sample history with `state_store` instead of
+    /// the default in-memory one, e.g. a persistent store that survives process restarts.
+    pub fn with_state_store(
+        url: impl IntoUrl,
+        filter_set: impl IntoIterator<Item = Filter>,
+        state_store: Box<dyn StateStore>,
+    ) -> Result<Self, Report> {
+        let filters = CompiledFilters::new(filter_set);
+        let max_relative_window = Self::max_relative_window(&filters);
         Ok(Self {
             url: url.into_url()?,
             client: reqwest::Client::builder().build()?,
-            filter_set: filter_set.into_iter().fold(
-                Default::default(),
-                |mut acc,
-                 Filter::Exact {
-                     metric_name,
-                     trigger,
-                 }| {
-                    acc.entry(metric_name).or_default().push(trigger);
-                    acc
-                },
-            ),
-            relative_state: Default::default(),
+            filters,
+            relative_state: state_store,
+            max_relative_window,
         })
     }
 
+    fn max_relative_window(filters: &CompiledFilters) -> chrono::Duration {
+        filters
+            .all_bounds()
+            .filter_map(Bound::time_period)
+            .max()
+            .unwrap_or(chrono::Duration::zero())
+    }
+
+    /// The endpoint this checker scrapes, used e.g. as the exporter's `element` label.
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    /// Replace this element's bound set in place, e.g. after a config reload. `relative_state`
+    /// keeps whatever history it already has; each metric's history is pruned to the (possibly
+    /// new) `max_relative_window` the next time a sample for that metric is written.
+    pub fn set_filters(&mut self, filter_set: impl IntoIterator<Item = Filter>) {
+        self.filters = CompiledFilters::new(filter_set);
+        self.max_relative_window = Self::max_relative_window(&self.filters);
+    }
+
     pub async fn check(&mut self) -> Result<Vec<Sample>, Report> {
-        Ok(self.check_filters(self.collect_prometheus_metrics().await?))
+        let metrics = self.collect_prometheus_metrics().await?;
+        Ok(self.check_filters(metrics, |_, _, _| {}))
+    }
+
+    /// Like [`Self::check`], but additionally records the violated/not-violated status of every
+    /// evaluated bound into `registry`, keyed by this element's URL.
+    pub async fn check_into_registry(&mut self, registry: &Registry) -> Result<Vec<Sample>, Report> {
+        let element = self.url.to_string();
+        let metrics = self.collect_prometheus_metrics().await?;
+        Ok(self.check_filters(metrics, |metric, bound, violated| {
+            registry.record(&element, metric, bound.descriptor(), violated);
+        }))
     }
 
     async fn collect_prometheus_metrics(&self) -> Result<prometheus_parse::Scrape, Report> {
@@ -84,7 +231,17 @@ impl ElementHealth {
         Ok(prometheus_parse::Scrape::parse(lines)?)
     }
 
-    fn check_filters(&mut self, curr_metrics: prometheus_parse::Scrape) -> Vec<Sample> {
+    /// Check `curr_metrics` against `self.filters` (exact-match, glob, and regex alike),
+    /// invoking `on_checked(metric, bound, violated)` for every bound evaluated (whether or not
+    /// it was violated), and returning the samples that violated at least one of their bounds.
+    /// Relative-bound history is always tracked under the sample's concrete metric name, never
+    /// the pattern that matched it, so each distinct metric name gets its own rate history.
+    /// Samples of one metric that differ only in their labels share that history.
+    fn check_filters(
+        &mut self,
+        curr_metrics: prometheus_parse::Scrape,
+        mut on_checked: impl FnMut(&str, &Bound, bool),
+    ) -> Vec<Sample> {
         curr_metrics
             .samples
             .into_iter()
@@ -95,27 +252,56 @@ impl ElementHealth {
                     ref timestamp,
                     ..
                 } = &sample;
-                if let Some(bounds) = self.filter_set.get(metric) {
-                    bounds.iter().any(|bound| {
-                        if bound.is_relative() {
-                            let existing = self
-                                .relative_state
-                                .entry(metric.clone())
-                                .or_insert(sample.clone());
-                            let old_value = &existing.value;
-                            let old_time = existing.timestamp;
-                            let res =
-                                bound.check(value, *timestamp, Some(old_value), Some(old_time));
-                            *existing = sample.clone();
-                            res
+
+                let bounds = self.filters.matching(metric);
+                if bounds.is_empty() {
+                    return false;
+                }
+
+                // Evaluate every bound unconditionally rather than short-circuiting on the
+                // first violation, so `on_checked` (and thus the exporter registry) sees every
+                // (metric, bound) pair's status on every scrape, not just the first violated one.
+                let violated = bounds
+                    .iter()
+                    .map(|bound| {
+                        let violated = if let Some(time_period) = bound.time_period() {
+                            let oldest =
+                                self.relative_state
+                                    .oldest_within(metric, *timestamp, time_period);
+                            bound.check(
+                                value,
+                                *timestamp,
+                                oldest.as_ref().map(|s| &s.value),
+                                oldest.as_ref().map(|s| s.timestamp),
+                            )
                         } else {
                             bound.check(value, *timestamp, None, None)
-                        }
+                        };
+                        on_checked(metric, bound, violated);
+                        violated
                     })
-                } else {
-                    false
+                    .collect::<Vec<_>>()
+                    .iter()
+                    .any(|v| *v);
+
+                // Record the sample once per scrape, after all bounds have read whatever
+                // history predates it, rather than once per relative bound.
+                if bounds.iter().any(Bound::is_relative) {
+                    self.relative_state
+                        .put(metric, sample.clone(), self.max_relative_window);
                 }
+
+                violated
             })
             .collect()
     }
 }
+
+/// Turn a URL into something usable as a path component, for the `sled` backend's per-element
+/// database directory.
+#[cfg(feature = "sled-backend")]
+fn sanitize_for_path(url: &str) -> String {
+    url.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
@@ -0,0 +1,170 @@
+//! Expose the agent's own health-check results as a scrapable Prometheus endpoint, so other
+//! tooling can alert on this agent's output instead of only seeing it printed to stderr.
+//!
+//! This is a pull-based exporter: [`Registry::record`] stashes the latest status per
+//! `(element, metric, bound)` series in a map guarded by a lock, and [`Registry::render`]
+//! regenerates the exposition text from that map on every scrape. Series whose owning element
+//! hasn't reported within `flush_window` are dropped so stale health bits don't linger forever.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use color_eyre::eyre::Report;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SeriesKey {
+    element: String,
+    metric: String,
+    bound: String,
+}
+
+struct Series {
+    violated: bool,
+    violations_total: u64,
+    last_report: Instant,
+}
+
+/// In-memory registry of this agent's own health-check results, keyed by
+/// `(element, metric, bound)`.
+pub struct Registry {
+    series: Mutex<HashMap<SeriesKey, Series>>,
+    last_scrape: Mutex<HashMap<String, SystemTime>>,
+    flush_window: Duration,
+}
+
+impl Registry {
+    pub fn new(flush_window: Duration) -> Self {
+        Self {
+            series: Mutex::new(HashMap::new()),
+            last_scrape: Mutex::new(HashMap::new()),
+            flush_window,
+        }
+    }
+
+    /// Record whether `bound` is currently violated for `metric` on `element`.
+    pub fn record(&self, element: &str, metric: &str, bound: &str, violated: bool) {
+        let key = SeriesKey {
+            element: element.to_owned(),
+            metric: metric.to_owned(),
+            bound: bound.to_owned(),
+        };
+        let mut series = self.series.lock().unwrap();
+        let entry = series.entry(key).or_insert_with(|| Series {
+            violated: false,
+            violations_total: 0,
+            last_report: Instant::now(),
+        });
+        entry.violated = violated;
+        if violated {
+            entry.violations_total += 1;
+        }
+        entry.last_report = Instant::now();
+
+        self.last_scrape
+            .lock()
+            .unwrap()
+            .insert(element.to_owned(), SystemTime::now());
+    }
+
+    /// Render the current registry contents in Prometheus text exposition format, dropping
+    /// series (and per-element scrape timestamps) that are older than `flush_window`.
+    pub fn render(&self) -> String {
+        let now = Instant::now();
+        let mut series = self.series.lock().unwrap();
+        series.retain(|_, s| now.duration_since(s.last_report) < self.flush_window);
+
+        let mut last_scrape = self.last_scrape.lock().unwrap();
+        let sys_now = SystemTime::now();
+        last_scrape.retain(|_, t| {
+            sys_now
+                .duration_since(*t)
+                .map(|age| age < self.flush_window)
+                .unwrap_or(true)
+        });
+
+        let mut out = String::new();
+        out.push_str("# HELP wtf_health_bit whether a configured bound is currently violated\n");
+        out.push_str("# TYPE wtf_health_bit gauge\n");
+        for (key, s) in series.iter() {
+            let _ = writeln!(
+                out,
+                "wtf_health_bit{{element=\"{}\",metric=\"{}\",bound=\"{}\"}} {}",
+                escape_label_value(&key.element),
+                escape_label_value(&key.metric),
+                escape_label_value(&key.bound),
+                s.violated as u8
+            );
+        }
+
+        out.push_str("# HELP wtf_health_violations_total total violations observed per series\n");
+        out.push_str("# TYPE wtf_health_violations_total counter\n");
+        for (key, s) in series.iter() {
+            let _ = writeln!(
+                out,
+                "wtf_health_violations_total{{element=\"{}\",metric=\"{}\",bound=\"{}\"}} {}",
+                escape_label_value(&key.element),
+                escape_label_value(&key.metric),
+                escape_label_value(&key.bound),
+                s.violations_total
+            );
+        }
+
+        out.push_str(
+            "# HELP wtf_health_last_scrape_timestamp_seconds unix time of the last reported check for this element\n",
+        );
+        out.push_str("# TYPE wtf_health_last_scrape_timestamp_seconds gauge\n");
+        for (element, t) in last_scrape.iter() {
+            let secs = t
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or(0.);
+            let element = escape_label_value(element);
+            let _ = writeln!(
+                out,
+                "wtf_health_last_scrape_timestamp_seconds{{element=\"{element}\"}} {secs}"
+            );
+        }
+
+        out
+    }
+}
+
+/// Escape a label value per the Prometheus text exposition format, so a scrape-target URL or
+/// metric name containing a quote, backslash, or newline can't break out of its surrounding
+/// quotes (or end the line early) and corrupt the rest of the scrape.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serve `registry` over HTTP on `listen`, regenerating the exposition text on every request.
+pub async fn serve(listen: SocketAddr, registry: Arc<Registry>) -> Result<(), Report> {
+    let listener = TcpListener::bind(listen).await?;
+    loop {
+        let (mut socket, _) = listener.accept().await?;
+        let registry = Arc::clone(&registry);
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            // We only ever serve one resource, so there's no need to parse the request beyond
+            // draining it off the socket.
+            if socket.read(&mut buf).await.is_err() {
+                return;
+            }
+
+            let body = registry.render();
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(resp.as_bytes()).await;
+        });
+    }
+}
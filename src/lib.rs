@@ -1,6 +1,6 @@
 //! Resources for querying Prometheus metrics.
 
-mod config_file;
+pub mod config_file;
 pub use config_file::{parse_config, parse_config_str};
 
 mod bound;
@@ -11,3 +11,17 @@ pub use element::ElementHealth;
 
 mod alert;
 pub use alert::AlertChecker;
+
+mod daemon;
+pub use daemon::Daemon;
+
+mod exporter;
+pub use exporter::{serve as serve_exporter, Registry};
+
+mod state_store;
+pub use state_store::{MemoryStateStore, StateStore};
+
+#[cfg(feature = "sled-backend")]
+mod sled_state_store;
+#[cfg(feature = "sled-backend")]
+pub use sled_state_store::SledStateStore;
@@ -0,0 +1,122 @@
+//! Persistent [`StateStore`] backend, gated behind the `sled-backend` feature. Samples survive
+//! process restarts, so rate bounds are correct immediately on startup instead of needing to
+//! warm back up over the next `time_period`.
+//!
+//! Samples are stored as `<metric>\0<timestamp_millis_be>` keys so a prefix scan for a metric
+//! comes back oldest-first, which is exactly the order [`StateStore::oldest_within`] needs.
+
+use chrono::{DateTime, TimeZone, Utc};
+use color_eyre::eyre::Report;
+use prometheus_parse::{Sample, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::state_store::StateStore;
+
+#[derive(Serialize, Deserialize)]
+struct StoredSample {
+    is_counter: bool,
+    value: f64,
+}
+
+impl StoredSample {
+    fn from_value(value: &Value) -> Option<Self> {
+        match *value {
+            Value::Counter(value) => Some(Self {
+                is_counter: true,
+                value,
+            }),
+            Value::Gauge(value) => Some(Self {
+                is_counter: false,
+                value,
+            }),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        if self.is_counter {
+            Value::Counter(self.value)
+        } else {
+            Value::Gauge(self.value)
+        }
+    }
+}
+
+pub struct SledStateStore {
+    db: sled::Db,
+}
+
+impl SledStateStore {
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, Report> {
+        Ok(Self {
+            db: sled::open(path)?,
+        })
+    }
+
+    fn prefix(metric: &str) -> Vec<u8> {
+        let mut prefix = metric.as_bytes().to_vec();
+        prefix.push(0);
+        prefix
+    }
+
+    fn key(metric: &str, timestamp: DateTime<Utc>) -> Vec<u8> {
+        let mut key = Self::prefix(metric);
+        key.extend_from_slice(&timestamp.timestamp_millis().to_be_bytes());
+        key
+    }
+
+    fn timestamp_of(prefix: &[u8], key: &[u8]) -> Option<DateTime<Utc>> {
+        let millis = i64::from_be_bytes(key[prefix.len()..].try_into().ok()?);
+        Utc.timestamp_millis_opt(millis).single()
+    }
+}
+
+impl StateStore for SledStateStore {
+    fn put(&mut self, metric: &str, sample: Sample, max_age: chrono::Duration) {
+        let Some(stored) = StoredSample::from_value(&sample.value) else {
+            return;
+        };
+        let Ok(encoded) = bincode::serialize(&stored) else {
+            return;
+        };
+        let _ = self.db.insert(Self::key(metric, sample.timestamp), encoded);
+
+        let cutoff = sample.timestamp - max_age;
+        let prefix = Self::prefix(metric);
+        let stale: Vec<_> = self
+            .db
+            .scan_prefix(&prefix)
+            .keys()
+            .filter_map(Result::ok)
+            .take_while(|key| {
+                Self::timestamp_of(&prefix, key).is_some_and(|timestamp| timestamp < cutoff)
+            })
+            .collect();
+        for key in stale {
+            let _ = self.db.remove(key);
+        }
+    }
+
+    fn oldest_within(
+        &self,
+        metric: &str,
+        now: DateTime<Utc>,
+        time_period: chrono::Duration,
+    ) -> Option<Sample> {
+        let prefix = Self::prefix(metric);
+        self.db.scan_prefix(&prefix).find_map(|entry| {
+            let (key, value) = entry.ok()?;
+            let timestamp = Self::timestamp_of(&prefix, &key)?;
+            if now - timestamp > time_period {
+                return None;
+            }
+            let stored: StoredSample = bincode::deserialize(&value).ok()?;
+            Some(Sample {
+                metric: metric.to_owned(),
+                value: stored.into_value(),
+                labels: Default::default(),
+                timestamp,
+            })
+        })
+    }
+}
@@ -0,0 +1,61 @@
+//! Pluggable backing store for relative-bound (`RateLower`/`RateUpper`) sample history.
+//!
+//! `ElementHealth` used to keep only the single most recent `Sample` per metric, so a rate bound
+//! was cold after every restart and couldn't see further back than the previous scrape. A
+//! `StateStore` instead retains a metric's samples across the largest configured `time_period`,
+//! letting `Bound::check` compare against the oldest sample still inside that window.
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use prometheus_parse::Sample;
+
+/// Per-metric timestamped sample history for relative bounds.
+pub trait StateStore: Send {
+    /// Record a newly-observed `sample` for `metric`, and drop any retained samples older than
+    /// `max_age` relative to it.
+    fn put(&mut self, metric: &str, sample: Sample, max_age: chrono::Duration);
+
+    /// The oldest retained sample for `metric` that is still within `time_period` of `now`, used
+    /// as the baseline for a rate computation. `None` if nothing has been retained yet.
+    fn oldest_within(
+        &self,
+        metric: &str,
+        now: DateTime<Utc>,
+        time_period: chrono::Duration,
+    ) -> Option<Sample>;
+}
+
+/// Default in-memory `StateStore`: a time-ordered queue of samples per metric, pruned on write.
+/// Lost on restart, same as the single-sample cache this replaces.
+#[derive(Default)]
+pub struct MemoryStateStore {
+    samples: HashMap<String, VecDeque<Sample>>,
+}
+
+impl StateStore for MemoryStateStore {
+    fn put(&mut self, metric: &str, sample: Sample, max_age: chrono::Duration) {
+        let history = self.samples.entry(metric.to_owned()).or_default();
+        let cutoff = sample.timestamp - max_age;
+        history.push_back(sample);
+        while history
+            .front()
+            .is_some_and(|oldest| oldest.timestamp < cutoff)
+        {
+            history.pop_front();
+        }
+    }
+
+    fn oldest_within(
+        &self,
+        metric: &str,
+        now: DateTime<Utc>,
+        time_period: chrono::Duration,
+    ) -> Option<Sample> {
+        self.samples
+            .get(metric)?
+            .iter()
+            .find(|s| now - s.timestamp <= time_period)
+            .cloned()
+    }
+}